@@ -0,0 +1,317 @@
+//! BIP32/BIP39 hierarchical key derivation on top of the sealed secret.
+//!
+//! The sealing key is a single 32-byte value; this module turns it into the
+//! root of a hierarchical tree so callers can derive many domain-separated
+//! keys deterministically via a derivation path, instead of being limited
+//! to exactly one derived key per seal.
+
+use anyhow::{Context, Result};
+use bip39::Mnemonic;
+use hmac::{Hmac, Mac};
+use sha2::Sha512;
+
+type HmacSha512 = Hmac<Sha512>;
+
+/// Elliptic curve used for hierarchical derivation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Curve {
+    Secp256k1,
+    Ed25519,
+}
+
+impl std::str::FromStr for Curve {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "secp256k1" => Ok(Curve::Secp256k1),
+            "ed25519" => Ok(Curve::Ed25519),
+            other => anyhow::bail!("invalid curve '{}' (expected secp256k1 or ed25519)", other),
+        }
+    }
+}
+
+/// An extended private key: a 32-byte key plus its 32-byte chain code.
+#[derive(Debug, Clone)]
+pub struct ExtendedKey {
+    pub key: [u8; 32],
+    pub chain_code: [u8; 32],
+}
+
+/// Smallest hardened child index, per BIP32 (`2^31`).
+pub const HARDENED_OFFSET: u32 = 0x8000_0000;
+
+/// Parse a derivation path like `m/44'/60'/0'/0/0` into index components,
+/// with hardened elements (denoted by a trailing `'` or `h`) offset by
+/// `HARDENED_OFFSET`.
+pub fn parse_path(path: &str) -> Result<Vec<u32>> {
+    let path = path.strip_prefix("m/").or_else(|| path.strip_prefix("m")).unwrap_or(path);
+    if path.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    path.split('/')
+        .map(|component| {
+            let (digits, hardened) = match component.strip_suffix('\'').or_else(|| component.strip_suffix('h')) {
+                Some(digits) => (digits, true),
+                None => (component, false),
+            };
+            let index: u32 = digits
+                .parse()
+                .with_context(|| format!("invalid path component '{}'", component))?;
+            if hardened {
+                Ok(index
+                    .checked_add(HARDENED_OFFSET)
+                    .context("path index too large to harden")?)
+            } else {
+                Ok(index)
+            }
+        })
+        .collect()
+}
+
+/// Use the 32-byte sealing key as BIP39 entropy, producing a mnemonic and
+/// the 64-byte BIP39 seed (`PBKDF2-HMAC-SHA512` over the mnemonic, per the
+/// BIP39 spec) used as the BIP32 seed.
+pub fn sealing_key_to_seed(sealing_key: &[u8; 32]) -> Result<(Mnemonic, [u8; 64])> {
+    let mnemonic = Mnemonic::from_entropy(sealing_key).context("failed to derive mnemonic from sealing key")?;
+    let seed = mnemonic.to_seed("");
+    Ok((mnemonic, seed))
+}
+
+/// Compute the BIP32 master key from a seed: `I = HMAC-SHA512(key="Bitcoin
+/// seed", data=seed)` for secp256k1 (or `key="ed25519 seed"` for ed25519,
+/// per SLIP-0010), split into `IL` (master private key) and `IR` (chain
+/// code).
+pub fn master_key_from_seed(seed: &[u8], curve: Curve) -> Result<ExtendedKey> {
+    let hmac_key: &[u8] = match curve {
+        Curve::Secp256k1 => b"Bitcoin seed",
+        Curve::Ed25519 => b"ed25519 seed",
+    };
+
+    let mut mac = HmacSha512::new_from_slice(hmac_key).expect("HMAC accepts any key length");
+    mac.update(seed);
+    let i = mac.finalize().into_bytes();
+
+    let mut key = [0u8; 32];
+    let mut chain_code = [0u8; 32];
+    key.copy_from_slice(&i[..32]);
+    chain_code.copy_from_slice(&i[32..]);
+
+    if curve == Curve::Secp256k1 {
+        validate_secp256k1_scalar(&key)?;
+    }
+
+    Ok(ExtendedKey { key, chain_code })
+}
+
+/// Derive a single child key at `index` from `parent`, per BIP32 (for
+/// secp256k1) or SLIP-0010 (for ed25519, which only supports hardened
+/// children).
+pub fn derive_child(parent: &ExtendedKey, index: u32, curve: Curve) -> Result<ExtendedKey> {
+    match curve {
+        Curve::Secp256k1 => derive_child_secp256k1(parent, index),
+        Curve::Ed25519 => derive_child_ed25519(parent, index),
+    }
+}
+
+/// Walk a parsed derivation path from `master`, deriving one child per
+/// path component.
+pub fn derive_path(master: &ExtendedKey, path: &[u32], curve: Curve) -> Result<ExtendedKey> {
+    let mut node = master.clone();
+    for &index in path {
+        node = derive_child(&node, index, curve)?;
+    }
+    Ok(node)
+}
+
+fn derive_child_secp256k1(parent: &ExtendedKey, index: u32) -> Result<ExtendedKey> {
+    use k256::elliptic_curve::sec1::ToEncodedPoint;
+    use k256::SecretKey;
+
+    let parent_scalar = scalar_from_bytes(&parent.key)?;
+
+    // If IL >= n or the resulting child key is zero, BIP32 says to retry
+    // with the next value of `index`, re-running the normal hardened/
+    // non-hardened formula (not some alternate encoding) against the same
+    // parent chain code. This has probability lower than 2^-127, so the
+    // loop runs exactly once in practice.
+    let mut current_index = index;
+    loop {
+        let mut data = Vec::with_capacity(37);
+        if current_index >= HARDENED_OFFSET {
+            // Hardened: I = HMAC-SHA512(chain_code, 0x00 || ser256(k_par) || ser32(index))
+            data.push(0x00);
+            data.extend_from_slice(&parent.key);
+        } else {
+            // Normal: I = HMAC-SHA512(chain_code, serP(point(k_par)) || ser32(index))
+            let parent_public = SecretKey::new(parent_scalar.into()).public_key();
+            data.extend_from_slice(parent_public.to_encoded_point(true).as_bytes());
+        }
+        data.extend_from_slice(&current_index.to_be_bytes());
+
+        let mut mac = HmacSha512::new_from_slice(&parent.chain_code).expect("HMAC accepts any key length");
+        mac.update(&data);
+        let i = mac.finalize().into_bytes();
+
+        let mut il = [0u8; 32];
+        let mut child_chain_code = [0u8; 32];
+        il.copy_from_slice(&i[..32]);
+        child_chain_code.copy_from_slice(&i[32..]);
+
+        if let Ok(il_scalar) = scalar_from_bytes(&il) {
+            let child_scalar = il_scalar + parent_scalar;
+            if !bool::from(k256::elliptic_curve::ff::Field::is_zero(&child_scalar)) {
+                let mut key = [0u8; 32];
+                key.copy_from_slice(&child_scalar.to_bytes());
+                return Ok(ExtendedKey { key, chain_code: child_chain_code });
+            }
+        }
+
+        current_index = current_index
+            .checked_add(1)
+            .context("BIP32 child index overflowed while retrying an invalid derived key")?;
+    }
+}
+
+fn derive_child_ed25519(parent: &ExtendedKey, index: u32) -> Result<ExtendedKey> {
+    if index < HARDENED_OFFSET {
+        anyhow::bail!("ed25519 (SLIP-0010) only supports hardened derivation; use a path element with '");
+    }
+
+    let mut data = Vec::with_capacity(37);
+    data.push(0x00);
+    data.extend_from_slice(&parent.key);
+    data.extend_from_slice(&index.to_be_bytes());
+
+    let mut mac = HmacSha512::new_from_slice(&parent.chain_code).expect("HMAC accepts any key length");
+    mac.update(&data);
+    let i = mac.finalize().into_bytes();
+
+    let mut key = [0u8; 32];
+    let mut chain_code = [0u8; 32];
+    key.copy_from_slice(&i[..32]);
+    chain_code.copy_from_slice(&i[32..]);
+
+    Ok(ExtendedKey { key, chain_code })
+}
+
+fn scalar_from_bytes(bytes: &[u8; 32]) -> Result<k256::Scalar> {
+    use k256::elliptic_curve::ff::PrimeField;
+
+    let scalar = k256::Scalar::from_repr((*bytes).into());
+    if bool::from(scalar.is_some()) {
+        Ok(scalar.unwrap())
+    } else {
+        anyhow::bail!("value is not a valid secp256k1 scalar (>= curve order n)")
+    }
+}
+
+fn validate_secp256k1_scalar(bytes: &[u8; 32]) -> Result<()> {
+    scalar_from_bytes(bytes).map(|_| ())
+}
+
+/// Compute the public key bytes for a derived private key, in the chosen
+/// curve's standard encoding (33-byte compressed SEC1 for secp256k1, 32-byte
+/// for ed25519).
+pub fn public_key_bytes(key: &ExtendedKey, curve: Curve) -> Result<Vec<u8>> {
+    match curve {
+        Curve::Secp256k1 => {
+            use k256::elliptic_curve::sec1::ToEncodedPoint;
+            use k256::SecretKey;
+
+            let secret = SecretKey::from_bytes((&key.key).into()).context("invalid secp256k1 private key")?;
+            Ok(secret.public_key().to_encoded_point(true).as_bytes().to_vec())
+        }
+        Curve::Ed25519 => {
+            use ed25519_dalek::SigningKey;
+
+            let signing_key = SigningKey::from_bytes(&key.key);
+            Ok(signing_key.verifying_key().to_bytes().to_vec())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEED: [u8; 64] = [0x5a; 64];
+
+    #[test]
+    fn parse_path_hardens_the_standard_bip44_prefix() {
+        let indices = parse_path("m/44'/60'/0'/0/0").unwrap();
+        assert_eq!(indices, vec![44 + HARDENED_OFFSET, 60 + HARDENED_OFFSET, HARDENED_OFFSET, 0, 0]);
+    }
+
+    #[test]
+    fn parse_path_rejects_an_index_too_large_to_harden() {
+        assert!(parse_path("m/4294967295'").is_err());
+    }
+
+    #[test]
+    fn master_key_from_seed_is_deterministic_per_curve() {
+        let a = master_key_from_seed(&SEED, Curve::Secp256k1).unwrap();
+        let b = master_key_from_seed(&SEED, Curve::Secp256k1).unwrap();
+        assert_eq!(a.key, b.key);
+        assert_eq!(a.chain_code, b.chain_code);
+
+        // The "Bitcoin seed" vs "ed25519 seed" HMAC key differs per curve,
+        // so the two masters must not collide.
+        let ed = master_key_from_seed(&SEED, Curve::Ed25519).unwrap();
+        assert_ne!(a.key, ed.key);
+    }
+
+    #[test]
+    fn derive_path_is_deterministic_and_distinct_per_index() {
+        let master = master_key_from_seed(&SEED, Curve::Secp256k1).unwrap();
+        let path = parse_path("m/44'/60'/0'/0/0").unwrap();
+        let other_path = parse_path("m/44'/60'/0'/0/1").unwrap();
+
+        let a = derive_path(&master, &path, Curve::Secp256k1).unwrap();
+        let b = derive_path(&master, &path, Curve::Secp256k1).unwrap();
+        assert_eq!(a.key, b.key);
+        assert_eq!(a.chain_code, b.chain_code);
+
+        let c = derive_path(&master, &other_path, Curve::Secp256k1).unwrap();
+        assert_ne!(a.key, c.key);
+    }
+
+    #[test]
+    fn hardened_and_normal_derivation_diverge_for_the_same_index() {
+        let master = master_key_from_seed(&SEED, Curve::Secp256k1).unwrap();
+        let hardened = derive_child(&master, HARDENED_OFFSET, Curve::Secp256k1).unwrap();
+        let normal = derive_child(&master, 0, Curve::Secp256k1).unwrap();
+        assert_ne!(hardened.key, normal.key);
+    }
+
+    #[test]
+    fn ed25519_rejects_non_hardened_indices() {
+        let master = master_key_from_seed(&SEED, Curve::Ed25519).unwrap();
+        assert!(derive_child(&master, 0, Curve::Ed25519).is_err());
+        assert!(derive_child(&master, HARDENED_OFFSET, Curve::Ed25519).is_ok());
+    }
+
+    #[test]
+    fn public_key_bytes_match_each_curve_s_standard_encoding() {
+        let secp = master_key_from_seed(&SEED, Curve::Secp256k1).unwrap();
+        assert_eq!(public_key_bytes(&secp, Curve::Secp256k1).unwrap().len(), 33);
+
+        let ed = master_key_from_seed(&SEED, Curve::Ed25519).unwrap();
+        assert_eq!(public_key_bytes(&ed, Curve::Ed25519).unwrap().len(), 32);
+    }
+
+    #[test]
+    fn sealing_key_to_seed_round_trips_through_the_full_pipeline() {
+        let sealing_key = [0x42u8; 32];
+        let (_mnemonic, seed_a) = sealing_key_to_seed(&sealing_key).unwrap();
+        let (_mnemonic, seed_b) = sealing_key_to_seed(&sealing_key).unwrap();
+        assert_eq!(seed_a, seed_b);
+
+        let master = master_key_from_seed(&seed_a, Curve::Secp256k1).unwrap();
+        let path = parse_path("m/44'/60'/0'/0/0").unwrap();
+        let child_a = derive_path(&master, &path, Curve::Secp256k1).unwrap();
+        let child_b = derive_path(&master, &path, Curve::Secp256k1).unwrap();
+        assert_eq!(child_a.key, child_b.key);
+    }
+}
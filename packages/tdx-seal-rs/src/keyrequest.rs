@@ -0,0 +1,153 @@
+//! TDG.KEY.REQUEST key-request subsystem.
+//!
+//! Modeled on the SGX EGETKEY KEYREQUEST: callers build a `KeyRequest`
+//! selecting which measurements the derived secret should be bound to, then
+//! issue the TDG.KEY.REQUEST TDCALL (behind the `tdx-guest` feature) to
+//! obtain a key that is reproducible only for TDs satisfying that binding.
+
+use anyhow::Result;
+
+#[cfg(feature = "tdx-guest")]
+use tdx_guest::tdcall::get_sealing_key as tdcall_get_sealing_key;
+
+/// Which TDX key to request, mirroring SGX's `KEYNAME` field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyName {
+    /// A secret usable to seal/unseal data across TD restarts.
+    Seal,
+    /// A secret that also reflects the contents of the current TDREPORT.
+    Report,
+}
+
+impl std::str::FromStr for KeyName {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "seal" => Ok(KeyName::Seal),
+            "report" => Ok(KeyName::Report),
+            other => anyhow::bail!("invalid key name '{}' (expected seal or report)", other),
+        }
+    }
+}
+
+/// Bitfield selecting which measurements the key is bound to, equivalent to
+/// SGX's `KEYPOLICY.MRENCLAVE`/`KEYPOLICY.MRSIGNER` bits. For TDX, binding
+/// to `MRTD` ties the key to this exact TD image; binding to
+/// `MRSIGNERSEAM` ties it to the TDX module signer instead, so the key
+/// survives a code upgrade that keeps the same signer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyPolicy(u32);
+
+impl KeyPolicy {
+    pub const MRTD: KeyPolicy = KeyPolicy(0x1);
+    pub const MRSIGNERSEAM: KeyPolicy = KeyPolicy(0x2);
+
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    pub const fn contains(self, other: KeyPolicy) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl std::fmt::Display for KeyPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.contains(KeyPolicy::MRTD), self.contains(KeyPolicy::MRSIGNERSEAM)) {
+            (true, true) => write!(f, "both"),
+            (true, false) => write!(f, "mrtd"),
+            (false, true) => write!(f, "mrsigner"),
+            (false, false) => write!(f, "none"),
+        }
+    }
+}
+
+impl std::ops::BitOr for KeyPolicy {
+    type Output = KeyPolicy;
+
+    fn bitor(self, rhs: KeyPolicy) -> KeyPolicy {
+        KeyPolicy(self.0 | rhs.0)
+    }
+}
+
+impl std::str::FromStr for KeyPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "mrtd" => Ok(KeyPolicy::MRTD),
+            "mrsigner" => Ok(KeyPolicy::MRSIGNERSEAM),
+            "both" => Ok(KeyPolicy::MRTD | KeyPolicy::MRSIGNERSEAM),
+            other => anyhow::bail!("invalid key policy '{}' (expected mrtd, mrsigner, or both)", other),
+        }
+    }
+}
+
+/// A TDG.KEY.REQUEST input, analogous to the SGX EGETKEY `KEYREQUEST`.
+#[derive(Debug, Clone)]
+pub struct KeyRequest {
+    pub keyname: KeyName,
+    pub keypolicy: KeyPolicy,
+    pub cpusvn: [u8; 16],
+    pub isvsvn: u16,
+    /// Caller-supplied salt allowing one TD to derive distinct keys for
+    /// distinct purposes from the same measurement binding.
+    pub keyid: [u8; 32],
+    /// XFAM mask recording which extended features are bound into the key.
+    pub xfam_mask: u64,
+}
+
+impl KeyRequest {
+    pub fn new(keyname: KeyName, keypolicy: KeyPolicy, keyid: [u8; 32]) -> Self {
+        KeyRequest {
+            keyname,
+            keypolicy,
+            cpusvn: [0u8; 16],
+            isvsvn: 0,
+            keyid,
+            xfam_mask: 0,
+        }
+    }
+}
+
+/// Issue the TDG.KEY.REQUEST TDCALL and return the resulting 32-byte
+/// sealing secret. Only `keypolicy`, `keyid` and `xfam_mask` are passed to
+/// the TDCALL; `keyname`, `cpusvn` and `isvsvn` are not.
+#[cfg(feature = "tdx-guest")]
+pub fn get_sealing_key(request: &KeyRequest) -> Result<[u8; 32]> {
+    let raw = tdcall_get_sealing_key(
+        request.keypolicy.bits(),
+        &request.keyid,
+        request.xfam_mask,
+    )
+    .map_err(|e| anyhow::anyhow!("TDG.KEY.REQUEST TDCALL failed: {:?}", e))?;
+
+    println!("Successfully derived sealing key (TDG.KEY.REQUEST, policy={:#x})", request.keypolicy.bits());
+    Ok(raw)
+}
+
+/// Simulated fallback used with `--simulate`. Hashes every field of the `KeyRequest` together with
+/// the measurement, so callers without TDX hardware can still exercise the
+/// rest of the pipeline, and two requests that differ only in `keyname`,
+/// `cpusvn`, `isvsvn`, or `xfam_mask` still derive distinct stub keys.
+pub fn simulate_sealing_key(request: &KeyRequest, measurement: &[u8; 32]) -> Result<[u8; 32]> {
+    use sha2::{Digest, Sha256};
+
+    let mut hasher = Sha256::new();
+    hasher.update(b"tdx-simulated-seal");
+    hasher.update([request.keyname as u8]);
+    hasher.update(request.keypolicy.bits().to_be_bytes());
+    hasher.update(request.cpusvn);
+    hasher.update(request.isvsvn.to_be_bytes());
+    hasher.update(request.keyid);
+    hasher.update(request.xfam_mask.to_be_bytes());
+    hasher.update(measurement);
+    let result = hasher.finalize();
+
+    let mut sealing_key = [0u8; 32];
+    sealing_key.copy_from_slice(&result);
+
+    println!("Successfully derived sealing key (simulated, policy={:#x})", request.keypolicy.bits());
+    Ok(sealing_key)
+}
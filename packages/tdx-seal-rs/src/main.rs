@@ -1,12 +1,18 @@
 use anyhow::Result;
 use clap::Parser;
-use hex;
-use sha2::{Digest, Sha256};
 use std::process;
 
-// Try to import tdx-guest, but handle the case where it's not available
-#[cfg(feature = "tdx-guest")]
-use tdx_guest::tdcall::get_tdinfo;
+mod hdkey;
+mod keyrequest;
+mod persistence;
+mod quote;
+mod report;
+mod sealing;
+
+use hdkey::Curve;
+use keyrequest::{KeyName, KeyPolicy, KeyRequest};
+use sealing::{Report, SealingProvider, TdxProvider, TpmProvider};
+use std::path::PathBuf;
 
 /// Intel TDX Sealing - Deterministic Private Key Derivation (Rust implementation)
 /// 
@@ -22,71 +28,69 @@ struct Args {
     /// Output format (hex, base64)
     #[arg(short, long, default_value = "hex")]
     format: String,
-}
 
-/// TDX error codes
-const TDX_SUCCESS: u32 = 0x00000000;
-const TDX_ERROR_INVALID_PARAMETER: u32 = 0x80000001;
-const TDX_ERROR_INVALID_OPERAND: u32 = 0x80000002;
-const TDX_ERROR_INVALID_OPERATION: u32 = 0x80000003;
-const TDX_ERROR_SEALING_NOT_AVAILABLE: u32 = 0x80000004;
-
-/// TDX feature and attribute bits
-const TDX_FEATURES0_SEALING_BIT: u64 = 0x00000001;
-const TDX_ATTRIBUTES_MIGRATABLE_BIT: u64 = 0x00000001;
-
-/// Domain separator for key derivation
-const DOMAIN_SEPARATOR: &str = "TDX_SEALING_PRIVATE_KEY_DERIVATION";
-
-/// TDX information structure
-#[derive(Debug, Clone)]
-struct TdInfo {
-    tdx_features0: u64,
-    tdx_attributes: u64,
-}
+    /// Measurements the sealing key is bound to: mrtd, mrsigner, or both
+    #[arg(long, default_value = "mrtd")]
+    key_policy: KeyPolicy,
 
-/// Get TDX information using the tdx-guest library
-fn get_tdx_info() -> Result<TdInfo> {
-    // Try to use the tdx-guest library
-    // The actual API may vary, so we'll implement a fallback approach
-    
-    // Attempt to call get_tdinfo from tdx-guest
-    // Note: The exact API may need to be adjusted based on the actual tdx-guest library
-    match try_get_tdinfo() {
-        Ok(td_info) => Ok(td_info),
-        Err(_) => {
-            // If tdx-guest is not available or we're not in a TDX environment,
-            // return an error to trigger the fallback simulation
-            anyhow::bail!("TDX not available in current environment")
-        }
-    }
-}
+    /// Key name for the key request: seal or report. It is mixed into the
+    /// --simulate stub key; the TDG.KEY.REQUEST TDCALL does not receive it
+    #[arg(long, default_value = "seal")]
+    key_name: KeyName,
 
-/// Attempt to get TDX info using the tdx-guest library
-fn try_get_tdinfo() -> Result<TdInfo> {
-    #[cfg(feature = "tdx-guest")]
-    {
-        // Try to use the actual tdx-guest library
-        match get_tdinfo() {
-            Ok(td_info) => {
-                // Convert from tdx-guest TdInfo to our TdInfo
-                // Note: The actual field names may vary based on the tdx-guest API
-                Ok(TdInfo {
-                    tdx_features0: td_info.tdx_features0,
-                    tdx_attributes: td_info.tdx_attributes,
-                })
-            }
-            Err(e) => {
-                anyhow::bail!("Failed to get TDX info: {:?}", e)
-            }
-        }
-    }
-    
-    #[cfg(not(feature = "tdx-guest"))]
-    {
-        // tdx-guest library not available
-        anyhow::bail!("TDX guest library not available (feature not enabled)")
-    }
+    /// Caller-supplied 32-byte key ID (hex), letting one TD derive distinct
+    /// keys for distinct purposes under the same key policy
+    #[arg(long)]
+    key_id: Option<String>,
+
+    /// Skip the TDG.MR.REPORT/TDG.KEY.REQUEST TDCALLs and use deterministic
+    /// stub values instead, for development off TDX hardware
+    #[arg(long)]
+    simulate: bool,
+
+    /// BIP32 derivation path applied to the sealed root, e.g. m/44'/60'/0'/0/0
+    #[arg(long, default_value = "m/44'/60'/0'/0/0")]
+    path: String,
+
+    /// Curve used for hierarchical key derivation
+    #[arg(long, default_value = "secp256k1")]
+    curve: Curve,
+
+    /// Sealing backend to use
+    #[arg(long, default_value = "auto")]
+    backend: String,
+
+    /// Comma-separated PCR indices the TPM backend binds the seal to
+    #[arg(long, default_value = "0,1,7")]
+    pcr: String,
+
+    /// Encrypt the derived key and write it to this file instead of
+    /// printing it to the terminal
+    #[arg(long)]
+    seal_out: Option<PathBuf>,
+
+    /// Decrypt a key previously written with --seal-out; the sealing key is
+    /// re-derived inside this TD/TPM and used as the AEAD key
+    #[arg(long)]
+    unseal_in: Option<PathBuf>,
+
+    /// Produce a DCAP attestation quote binding the derived public key into
+    /// the TD report, in addition to the normal output
+    #[arg(long)]
+    attest: bool,
+
+    /// Write the attestation quote generated by --attest to this file
+    #[arg(long)]
+    attest_out: Option<PathBuf>,
+
+    /// Verify a DCAP quote file against an expected public key instead of
+    /// deriving a key; does not require TDX hardware
+    #[arg(long)]
+    verify_quote: Option<PathBuf>,
+
+    /// Expected public key (hex) for --verify-quote
+    #[arg(long)]
+    public_key: Option<String>,
 }
 
 /// Check if running as root (on Unix-like systems)
@@ -105,74 +109,38 @@ fn check_root_privileges() -> Result<()> {
     Ok(())
 }
 
-/// Check TDX sealing availability by examining TDX_FEATURES0.SEALING bit
-fn check_tdx_features(td_info: &TdInfo) -> Result<()> {
-    // Check SEALING bit in TDX_FEATURES0
-    if !(td_info.tdx_features0 & TDX_FEATURES0_SEALING_BIT != 0) {
-        anyhow::bail!("TDX sealing is not available (TDX_FEATURES0.SEALING = 0)");
-    }
-    
-    println!("TDX_FEATURES0.SEALING = 1 (sealing available)");
-    Ok(())
+/// Build every sealing backend, in probe order for `--backend auto`.
+fn build_providers(args: &Args, pcrs: Vec<u8>) -> Vec<Box<dyn SealingProvider>> {
+    vec![
+        Box::new(TdxProvider { simulate: args.simulate }),
+        Box::new(TpmProvider { pcrs, simulate: args.simulate }),
+    ]
 }
 
-/// Check TDX attributes by examining ATTRIBUTES.MIGRATABLE bit
-fn check_tdx_attributes(td_info: &TdInfo) -> Result<()> {
-    // Check MIGRATABLE bit in TDX_ATTRIBUTES
-    if td_info.tdx_attributes & TDX_ATTRIBUTES_MIGRATABLE_BIT != 0 {
-        anyhow::bail!("TDX sealing is not available (ATTRIBUTES.MIGRATABLE = 1)");
-    }
-    
-    println!("ATTRIBUTES.MIGRATABLE = 0 (sealing available)");
-    Ok(())
+/// Parse a comma-separated PCR index list like `0,1,7`.
+fn parse_pcr_list(pcrs: &str) -> Result<Vec<u8>> {
+    pcrs.split(',')
+        .map(|s| s.trim().parse::<u8>().map_err(|_| anyhow::anyhow!("invalid PCR index '{}'", s)))
+        .collect()
 }
 
-/// Get TDX measurement report (simulated for this implementation)
-/// In a real implementation, this would use TDG.MR.REPORT
-fn get_tdx_measurement_report() -> Result<[u8; 32]> {
-    // For this implementation, we'll simulate getting the measurement report
-    // In a real TDX environment, this would call TDG.MR.REPORT TDCALL
-    let mut mrenclave = [0u8; 32];
-    
-    // Simulate MRENCLAVE value (in real implementation, this comes from TDG.MR.REPORT)
-    for i in 0..32 {
-        mrenclave[i] = (i as u8).wrapping_add(0xAB);
-    }
-    
-    println!("Successfully obtained TDX measurement report (simulated)");
-    Ok(mrenclave)
-}
-
-/// Request sealing key using MRENCLAVE (simulated for this implementation)
-/// In a real implementation, this would use TDG.KEY.REQUEST
-fn get_sealing_key(mrenclave: &[u8; 32]) -> Result<[u8; 32]> {
-    // For this implementation, we'll simulate getting the sealing key
-    // In a real TDX environment, this would call TDG.KEY.REQUEST TDCALL
-    let mut sealing_key = [0u8; 32];
-    
-    // Simulate sealing key derivation (in real implementation, this comes from TDG.KEY.REQUEST)
-    for i in 0..32 {
-        sealing_key[i] = mrenclave[i].wrapping_add(0xCD);
-    }
-    
-    println!("Successfully derived sealing key (simulated)");
-    Ok(sealing_key)
-}
+/// Derive the hierarchical child key at `path` from the sealing key, using
+/// the sealing key as BIP39 entropy to seed a BIP32 (or SLIP-0010, for
+/// ed25519) master key. Returns the mnemonic (for display/backup), the
+/// derived extended key, and its public key encoding.
+fn derive_hierarchical_key(
+    sealing_key: &[u8; 32],
+    path: &str,
+    curve: Curve,
+) -> Result<(bip39::Mnemonic, hdkey::ExtendedKey, Vec<u8>)> {
+    let (mnemonic, seed) = hdkey::sealing_key_to_seed(sealing_key)?;
+    let master = hdkey::master_key_from_seed(&seed, curve)?;
+    let path_indices = hdkey::parse_path(path)?;
+    let child = hdkey::derive_path(&master, &path_indices, curve)?;
+    let public_key = hdkey::public_key_bytes(&child, curve)?;
 
-/// Derive deterministic private key from sealing key using SHA-256
-fn derive_private_key(sealing_key: &[u8; 32]) -> Result<[u8; 32]> {
-    let mut hasher = Sha256::new();
-    
-    // Hash the domain separator and sealing key
-    hasher.update(DOMAIN_SEPARATOR.as_bytes());
-    hasher.update(sealing_key);
-    
-    let result = hasher.finalize();
-    let mut private_key = [0u8; 32];
-    private_key.copy_from_slice(&result);
-    
-    println!("Successfully derived deterministic private key");
-    Ok(private_key)
+    println!("Successfully derived hierarchical key at path {}", path);
+    Ok((mnemonic, child, public_key))
 }
 
 /// Print hex data
@@ -191,6 +159,20 @@ fn print_hex(label: &str, data: &[u8], format: &str) {
     }
 }
 
+/// Parse a caller-supplied hex key ID into a 32-byte buffer, defaulting to
+/// all zeroes when none is given.
+fn parse_key_id(key_id: Option<&str>) -> Result<[u8; 32]> {
+    let mut keyid = [0u8; 32];
+    if let Some(hex_str) = key_id {
+        let bytes = hex::decode(hex_str)?;
+        if bytes.len() != 32 {
+            anyhow::bail!("--key-id must be exactly 32 bytes (64 hex characters), got {}", bytes.len());
+        }
+        keyid.copy_from_slice(&bytes);
+    }
+    Ok(keyid)
+}
+
 /// Securely zero memory
 fn secure_zero_memory(data: &mut [u8]) {
     for byte in data.iter_mut() {
@@ -200,7 +182,49 @@ fn secure_zero_memory(data: &mut [u8]) {
 
 fn main() {
     let args = Args::parse();
-    
+
+    // --verify-quote is a standalone relying-party mode: it checks a quote
+    // against an expected public key and never touches TDX/TPM hardware.
+    if let Some(quote_path) = &args.verify_quote {
+        let public_key_hex = match &args.public_key {
+            Some(hex_str) => hex_str,
+            None => {
+                eprintln!("Error: --verify-quote requires --public-key");
+                process::exit(1);
+            }
+        };
+        let public_key = match hex::decode(public_key_hex) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                eprintln!("Error: Invalid --public-key: {}", e);
+                process::exit(1);
+            }
+        };
+        let raw_quote = match std::fs::read(quote_path) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                eprintln!("Error: Failed to read {}: {}", quote_path.display(), e);
+                process::exit(1);
+            }
+        };
+        match quote::verify_quote(&raw_quote, &public_key) {
+            Ok(td_report) => {
+                println!("Quote verified: REPORTDATA matches the presented public key");
+                print_hex("MRTD", &td_report.mr_td, &args.format);
+                print_hex("MRSIGNERSEAM", &td_report.mr_signer_seam, &args.format);
+                print_hex("TEE_TCB_SVN", &td_report.tee_tcb_svn, &args.format);
+                for (i, rtmr) in td_report.rtmrs.iter().enumerate() {
+                    print_hex(&format!("RTMR{}", i), rtmr, &args.format);
+                }
+                return;
+            }
+            Err(e) => {
+                eprintln!("Error: Quote verification failed: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+
     if args.verbose {
         println!("Intel TDX Sealing - Deterministic Private Key Derivation (Rust)");
         println!("===============================================================\n");
@@ -211,90 +235,163 @@ fn main() {
         eprintln!("Error: {}", e);
         process::exit(1);
     }
-    
-    // Get TDX information using the tdx-guest library
-    let td_info = match get_tdx_info() {
-        Ok(info) => {
-            if args.verbose {
-                println!("Successfully retrieved TDX information using tdx-guest library");
-            }
-            info
+
+    // Parse the PCR list and select a sealing backend
+    let pcrs = match parse_pcr_list(&args.pcr) {
+        Ok(pcrs) => pcrs,
+        Err(e) => {
+            eprintln!("Error: Invalid --pcr: {}", e);
+            process::exit(1);
         }
+    };
+    let provider = match sealing::select_provider(&args.backend, build_providers(&args, pcrs)) {
+        Ok(provider) => provider,
         Err(e) => {
-            eprintln!("Error: Failed to retrieve TDX information: {:?}", e);
-            eprintln!("Note: This may be expected if not running in a TDX environment");
-            
-            // For demonstration purposes, create a mock TDX info
-            // In a real TDX environment, this would not be needed
-            if args.verbose {
-                println!("Creating mock TDX info for demonstration...");
-            }
-            
-            TdInfo {
-                tdx_features0: TDX_FEATURES0_SEALING_BIT,
-                tdx_attributes: 0, // MIGRATABLE = 0
-            }
+            eprintln!("Error: Failed to select sealing backend: {}", e);
+            process::exit(1);
         }
     };
-    
-    // Check TDX sealing availability
-    if let Err(e) = check_tdx_features(&td_info) {
-        eprintln!("Error: {}", e);
-        process::exit(1);
-    }
-    
-    if let Err(e) = check_tdx_attributes(&td_info) {
-        eprintln!("Error: {}", e);
-        process::exit(1);
+    if args.verbose {
+        println!("Using sealing backend: {}", provider.name());
     }
-    
-    // Get TDX measurement report
-    let mrenclave = match get_tdx_measurement_report() {
-        Ok(mr) => mr,
+
+    // Get the measurement report from the selected backend
+    let measurement_report = match provider.measurement() {
+        Ok(report) => report,
         Err(e) => {
-            eprintln!("Error: Failed to get TDX measurement report: {}", e);
+            eprintln!("Error: Failed to get measurement report: {}", e);
             process::exit(1);
         }
     };
-    
-    // Print MRENCLAVE
-    print_hex("MRENCLAVE", &mrenclave, &args.format);
-    
-    // Request sealing key
-    let sealing_key = match get_sealing_key(&mrenclave) {
-        Ok(key) => key,
+
+    match &measurement_report {
+        Report::Tdx(td_report) => {
+            print_hex("MRTD", &td_report.mr_td, &args.format);
+            for (i, rtmr) in td_report.rtmrs.iter().enumerate() {
+                print_hex(&format!("RTMR{}", i), rtmr, &args.format);
+            }
+        }
+        Report::Tpm(pcr_report) => {
+            for (index, digest) in &pcr_report.pcrs {
+                print_hex(&format!("PCR{}", index), digest, &args.format);
+            }
+        }
+    }
+
+    // Parse the caller-supplied key ID, defaulting to all zeroes
+    let keyid = match parse_key_id(args.key_id.as_deref()) {
+        Ok(id) => id,
         Err(e) => {
-            eprintln!("Error: Failed to get sealing key: {}", e);
+            eprintln!("Error: Invalid --key-id: {}", e);
             process::exit(1);
         }
     };
-    
-    // Print sealing key
-    print_hex("Sealing Key", &sealing_key, &args.format);
-    
-    // Derive deterministic private key
-    let private_key = match derive_private_key(&sealing_key) {
+    let key_request = KeyRequest::new(args.key_name, args.key_policy, keyid);
+
+    // Request sealing key from the selected backend
+    let sealing_key = match provider.seal_key(&key_request) {
         Ok(key) => key,
         Err(e) => {
-            eprintln!("Error: Failed to derive private key: {}", e);
+            eprintln!("Error: Failed to get sealing key: {}", e);
             process::exit(1);
         }
     };
-    
-    // Print derived private key
-    print_hex("Derived Private Key", &private_key, &args.format);
-    
+
+    // The sealing key is sensitive: it's both the HKDF input for the
+    // --seal-out/--unseal-in AEAD key and the BIP39 entropy for every key
+    // derivable at any path/curve. Never print it when a seal/unseal flow
+    // is in play, and even then only with --verbose (matching the original
+    // "never expose the plaintext key" intent of persisting it at all).
+    if args.verbose && args.seal_out.is_none() && args.unseal_in.is_none() {
+        print_hex("Sealing Key", &sealing_key, &args.format);
+    }
+
+    // --unseal-in re-derives the sealing key above inside this TD/TPM, then
+    // uses it as the AEAD key to decrypt a previously-sealed container; it
+    // never runs hierarchical derivation itself.
+    if let Some(in_path) = &args.unseal_in {
+        let unsealed_key = match persistence::unseal_key_from_file(in_path, &sealing_key) {
+            Ok(key) => key,
+            Err(e) => {
+                eprintln!("Error: Failed to unseal {}: {}", in_path.display(), e);
+                process::exit(1);
+            }
+        };
+        print_hex("Unsealed Private Key", &unsealed_key, &args.format);
+
+        let mut sealing_key_mut = sealing_key;
+        let mut unsealed_key_mut = unsealed_key;
+        secure_zero_memory(&mut sealing_key_mut);
+        secure_zero_memory(&mut unsealed_key_mut);
+        return;
+    }
+
+    // Derive the hierarchical child key at --path from the sealing key
+    let (mnemonic, child_key, public_key) =
+        match derive_hierarchical_key(&sealing_key, &args.path, args.curve) {
+            Ok(result) => result,
+            Err(e) => {
+                eprintln!("Error: Failed to derive hierarchical key: {}", e);
+                process::exit(1);
+            }
+        };
+
+    if args.verbose {
+        println!("Mnemonic: {}", mnemonic);
+    }
+    print_hex("Derived Public Key", &public_key, &args.format);
+
+    // --attest binds the derived public key into a DCAP quote for remote
+    // verification
+    if args.attest {
+        match quote::generate_quote(&public_key, args.simulate) {
+            Ok(attestation) => {
+                print_hex("Quote", &attestation.raw, &args.format);
+                print_hex("Quote MRTD", &attestation.td_report.mr_td, &args.format);
+                if let Some(attest_out) = &args.attest_out {
+                    if let Err(e) = std::fs::write(attest_out, &attestation.raw) {
+                        eprintln!("Error: Failed to write quote to {}: {}", attest_out.display(), e);
+                        process::exit(1);
+                    }
+                    println!("Wrote attestation quote to {}", attest_out.display());
+                }
+            }
+            Err(e) => {
+                eprintln!("Error: Failed to generate attestation quote: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+
+    // --seal-out persists the derived key encrypted at rest instead of
+    // printing it in the clear.
+    if let Some(out_path) = &args.seal_out {
+        let measurement = measurement_report.measurement_bytes();
+        if let Err(e) = persistence::seal_key_to_file(
+            out_path,
+            &child_key.key,
+            &sealing_key,
+            &args.key_policy.to_string(),
+            &args.path,
+            &measurement,
+        ) {
+            eprintln!("Error: Failed to seal key to {}: {}", out_path.display(), e);
+            process::exit(1);
+        }
+        println!("Sealed derived private key to {}", out_path.display());
+    } else {
+        print_hex("Derived Private Key", &child_key.key, &args.format);
+    }
+
     println!("\nSuccessfully derived deterministic private key using TDX sealing!");
-    
+
     // Securely clear sensitive data
-    let mut mrenclave_mut = mrenclave;
     let mut sealing_key_mut = sealing_key;
-    let mut private_key_mut = private_key;
-    
-    secure_zero_memory(&mut mrenclave_mut);
+    let mut private_key_mut = child_key.key;
+
     secure_zero_memory(&mut sealing_key_mut);
     secure_zero_memory(&mut private_key_mut);
-    
+
     if args.verbose {
         println!("Sensitive data securely cleared from memory");
     }
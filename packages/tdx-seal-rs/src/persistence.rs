@@ -0,0 +1,193 @@
+//! Persist a derived key to disk instead of printing it to the terminal.
+//!
+//! The derived key is encrypted at rest with AES-256-GCM under an AEAD key
+//! derived (via HKDF) from the sealing key, so the resulting blob is only
+//! decryptable by a TD/TPM that can re-derive the same sealing key — i.e.
+//! one satisfying the same measurement binding that created it.
+
+use std::fs;
+use std::path::Path;
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use anyhow::{Context, Result};
+use hkdf::Hkdf;
+use rand::RngCore;
+use sha2::Sha256;
+
+/// Info string binding the HKDF output to this specific use, so the same
+/// sealing key can't be reused as an AEAD key for an unrelated purpose.
+const HKDF_INFO: &[u8] = b"tdx-seal-rs sealed-key-container v1";
+
+/// Length of the AES-GCM nonce/IV in bytes.
+const IV_LEN: usize = 12;
+
+/// On-disk container: a header recording how the key was derived, plus the
+/// IV and AEAD ciphertext (which includes the authentication tag).
+#[derive(Debug, Clone)]
+pub struct SealedContainer {
+    pub key_policy: String,
+    pub path: String,
+    pub measurement: Vec<u8>,
+    pub iv: [u8; IV_LEN],
+    pub ciphertext: Vec<u8>,
+}
+
+/// Derive the AEAD key used to seal/unseal a container from the sealing key.
+fn aead_key_from_sealing_key(sealing_key: &[u8; 32]) -> [u8; 32] {
+    let hk = Hkdf::<Sha256>::new(None, sealing_key);
+    let mut aead_key = [0u8; 32];
+    hk.expand(HKDF_INFO, &mut aead_key)
+        .expect("32 is a valid HKDF-SHA256 output length");
+    aead_key
+}
+
+/// Encrypt `key` under a fresh IV and write the container to `out_path`.
+pub fn seal_key_to_file(
+    out_path: &Path,
+    key: &[u8; 32],
+    sealing_key: &[u8; 32],
+    key_policy: &str,
+    path: &str,
+    measurement: &[u8],
+) -> Result<()> {
+    let aead_key = aead_key_from_sealing_key(sealing_key);
+    let cipher = Aes256Gcm::new_from_slice(&aead_key).context("invalid AEAD key length")?;
+
+    let mut iv = [0u8; IV_LEN];
+    rand::thread_rng().fill_bytes(&mut iv);
+    let nonce = Nonce::from_slice(&iv);
+
+    let ciphertext = cipher
+        .encrypt(nonce, key.as_slice())
+        .map_err(|e| anyhow::anyhow!("AES-256-GCM encryption failed: {}", e))?;
+
+    let container = SealedContainer {
+        key_policy: key_policy.to_string(),
+        path: path.to_string(),
+        measurement: measurement.to_vec(),
+        iv,
+        ciphertext,
+    };
+
+    fs::write(out_path, encode(&container)).with_context(|| format!("failed to write {}", out_path.display()))
+}
+
+/// Read a container from `in_path` and decrypt it with the AEAD key derived
+/// from `sealing_key`, returning the original 32-byte key on success (which
+/// only happens if `sealing_key` matches the one used to seal it).
+pub fn unseal_key_from_file(in_path: &Path, sealing_key: &[u8; 32]) -> Result<[u8; 32]> {
+    let raw = fs::read(in_path).with_context(|| format!("failed to read {}", in_path.display()))?;
+    let container = decode(&raw)?;
+
+    let aead_key = aead_key_from_sealing_key(sealing_key);
+    let cipher = Aes256Gcm::new_from_slice(&aead_key).context("invalid AEAD key length")?;
+    let nonce = Nonce::from_slice(&container.iv);
+
+    let plaintext = cipher
+        .decrypt(nonce, container.ciphertext.as_slice())
+        .map_err(|_| anyhow::anyhow!("failed to unseal: wrong TD/TPM measurement or corrupted file"))?;
+
+    if plaintext.len() != 32 {
+        anyhow::bail!("unsealed key has unexpected length {} (expected 32)", plaintext.len());
+    }
+    let mut key = [0u8; 32];
+    key.copy_from_slice(&plaintext);
+
+    println!("Successfully unsealed key (policy={}, path={})", container.key_policy, container.path);
+    Ok(key)
+}
+
+/// Encode a container as a simple length-prefixed binary format:
+/// `[u32 key_policy_len][key_policy][u32 path_len][path][u32 measurement_len][measurement][iv; 12][ciphertext]`.
+fn encode(container: &SealedContainer) -> Vec<u8> {
+    let mut buf = Vec::new();
+    write_field(&mut buf, container.key_policy.as_bytes());
+    write_field(&mut buf, container.path.as_bytes());
+    write_field(&mut buf, &container.measurement);
+    buf.extend_from_slice(&container.iv);
+    buf.extend_from_slice(&container.ciphertext);
+    buf
+}
+
+fn write_field(buf: &mut Vec<u8>, field: &[u8]) {
+    buf.extend_from_slice(&(field.len() as u32).to_be_bytes());
+    buf.extend_from_slice(field);
+}
+
+fn decode(raw: &[u8]) -> Result<SealedContainer> {
+    let mut cursor = 0usize;
+
+    let key_policy = read_field(raw, &mut cursor)?;
+    let path = read_field(raw, &mut cursor)?;
+    let measurement = read_field(raw, &mut cursor)?;
+
+    if raw.len() < cursor + IV_LEN {
+        anyhow::bail!("sealed container is truncated (missing IV)");
+    }
+    let mut iv = [0u8; IV_LEN];
+    iv.copy_from_slice(&raw[cursor..cursor + IV_LEN]);
+    cursor += IV_LEN;
+
+    let ciphertext = raw[cursor..].to_vec();
+
+    Ok(SealedContainer {
+        key_policy: String::from_utf8(key_policy).context("key policy field is not valid UTF-8")?,
+        path: String::from_utf8(path).context("path field is not valid UTF-8")?,
+        measurement,
+        iv,
+        ciphertext,
+    })
+}
+
+fn read_field(raw: &[u8], cursor: &mut usize) -> Result<Vec<u8>> {
+    if raw.len() < *cursor + 4 {
+        anyhow::bail!("sealed container is truncated (missing length prefix)");
+    }
+    let mut len_bytes = [0u8; 4];
+    len_bytes.copy_from_slice(&raw[*cursor..*cursor + 4]);
+    let len = u32::from_be_bytes(len_bytes) as usize;
+    *cursor += 4;
+
+    if raw.len() < *cursor + len {
+        anyhow::bail!("sealed container is truncated (field shorter than declared length)");
+    }
+    let field = raw[*cursor..*cursor + len].to_vec();
+    *cursor += len;
+    Ok(field)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seal_and_unseal_round_trip() {
+        let out_path = std::env::temp_dir().join(format!("tdx-seal-rs-test-{}.bin", std::process::id()));
+
+        let key = [0x11u8; 32];
+        let sealing_key = [0x22u8; 32];
+        let measurement = vec![0x33u8; 48];
+
+        seal_key_to_file(&out_path, &key, &sealing_key, "mrtd", "m/44'/60'/0'/0/0", &measurement).unwrap();
+        let unsealed = unseal_key_from_file(&out_path, &sealing_key).unwrap();
+        assert_eq!(unsealed, key);
+
+        fs::remove_file(&out_path).unwrap();
+    }
+
+    #[test]
+    fn unseal_fails_with_the_wrong_sealing_key() {
+        let out_path =
+            std::env::temp_dir().join(format!("tdx-seal-rs-test-wrong-key-{}.bin", std::process::id()));
+
+        let key = [0x11u8; 32];
+        let sealing_key = [0x22u8; 32];
+        let wrong_sealing_key = [0x99u8; 32];
+
+        seal_key_to_file(&out_path, &key, &sealing_key, "mrtd", "m/44'/60'/0'/0/0", &[]).unwrap();
+        assert!(unseal_key_from_file(&out_path, &wrong_sealing_key).is_err());
+
+        fs::remove_file(&out_path).unwrap();
+    }
+}
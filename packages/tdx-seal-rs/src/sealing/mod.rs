@@ -0,0 +1,77 @@
+//! Pluggable sealing backends.
+//!
+//! Not every deployment has TDX; some have a TPM instead. The
+//! `SealingProvider` trait abstracts over "get a measurement" and "seal a
+//! key to that measurement" so the rest of the pipeline (hierarchical
+//! derivation, persistence, attestation) doesn't need to know which
+//! hardware root of trust it's running on.
+
+mod tdx;
+mod tpm;
+
+use anyhow::Result;
+
+use crate::keyrequest::KeyRequest;
+use crate::report::TdReport;
+
+pub use tdx::TdxProvider;
+pub use tpm::TpmProvider;
+
+/// A measurement obtained from a sealing backend, used to bind a derived
+/// key to a particular platform/software state.
+#[derive(Debug, Clone)]
+pub enum Report {
+    /// A full TDX TDREPORT. Boxed since it's far larger than `TpmPcrReport`.
+    Tdx(Box<TdReport>),
+    /// A TPM 2.0 PCR selection and its digests.
+    Tpm(TpmPcrReport),
+}
+
+impl Report {
+    /// Flatten the report into raw bytes suitable for recording in a sealed
+    /// container header (not for re-deriving keys from — just a label).
+    pub fn measurement_bytes(&self) -> Vec<u8> {
+        match self {
+            Report::Tdx(report) => report.mr_td.to_vec(),
+            Report::Tpm(report) => report.pcrs.iter().flat_map(|(_, digest)| digest.to_vec()).collect(),
+        }
+    }
+}
+
+/// The PCR indices and digests a TPM-sealed key was bound to.
+#[derive(Debug, Clone)]
+pub struct TpmPcrReport {
+    pub pcrs: Vec<(u8, [u8; 32])>,
+}
+
+/// A hardware root of trust capable of producing a measurement and sealing
+/// a key to it.
+pub trait SealingProvider {
+    /// Human-readable backend name, matched against `--backend <name>` and
+    /// shown in verbose output.
+    fn name(&self) -> &'static str;
+
+    /// Whether this backend is usable in the current environment (feature
+    /// compiled in, and hardware/interface reachable).
+    fn available(&self) -> bool;
+
+    /// Obtain the current measurement (TD measurement or PCR state).
+    fn measurement(&self) -> Result<Report>;
+
+    /// Seal a key bound to the current measurement and `request`.
+    fn seal_key(&self, request: &KeyRequest) -> Result<[u8; 32]>;
+}
+
+/// Select a provider by name, probing `available()` in order for `auto`.
+pub fn select_provider(backend: &str, providers: Vec<Box<dyn SealingProvider>>) -> Result<Box<dyn SealingProvider>> {
+    match backend {
+        "auto" => providers
+            .into_iter()
+            .find(|p| p.available())
+            .ok_or_else(|| anyhow::anyhow!("no sealing backend is available (tried tdx, tpm)")),
+        name => providers
+            .into_iter()
+            .find(|p| p.name() == name)
+            .ok_or_else(|| anyhow::anyhow!("unknown sealing backend '{}'", name)),
+    }
+}
@@ -0,0 +1,98 @@
+//! `SealingProvider` implementation backed by Intel TDX.
+
+use anyhow::Result;
+
+use super::{Report, SealingProvider};
+use crate::keyrequest::KeyRequest;
+use crate::report;
+
+#[cfg(feature = "tdx-guest")]
+use tdx_guest::tdcall::get_tdinfo;
+
+/// TDX feature and attribute bits, checked before trusting a real
+/// TDG.MR.REPORT/TDG.KEY.REQUEST result.
+#[cfg(feature = "tdx-guest")]
+const TDX_FEATURES0_SEALING_BIT: u64 = 0x00000001;
+#[cfg(feature = "tdx-guest")]
+const TDX_ATTRIBUTES_MIGRATABLE_BIT: u64 = 0x00000001;
+
+/// Intel TDX sealing backend: TDG.MR.REPORT for measurement, TDG.KEY.REQUEST
+/// for sealing. Uses deterministic stub values instead when `simulate` is
+/// set (the CLI's `--simulate`).
+pub struct TdxProvider {
+    pub simulate: bool,
+}
+
+impl SealingProvider for TdxProvider {
+    fn name(&self) -> &'static str {
+        "tdx"
+    }
+
+    fn available(&self) -> bool {
+        if self.simulate {
+            return true;
+        }
+
+        #[cfg(feature = "tdx-guest")]
+        {
+            return check_tdx_sealing_available().is_ok();
+        }
+
+        #[cfg(not(feature = "tdx-guest"))]
+        false
+    }
+
+    fn measurement(&self) -> Result<Report> {
+        if self.simulate {
+            return report::simulate_tdx_measurement_report().map(|r| Report::Tdx(Box::new(r)));
+        }
+
+        #[cfg(feature = "tdx-guest")]
+        {
+            check_tdx_sealing_available()?;
+            let report_data = [0u8; 64];
+            return report::get_tdx_measurement_report(&report_data).map(|r| Report::Tdx(Box::new(r)));
+        }
+
+        #[cfg(not(feature = "tdx-guest"))]
+        anyhow::bail!("TDG.MR.REPORT requires the 'tdx-guest' feature; pass --simulate to use a stub report instead")
+    }
+
+    fn seal_key(&self, request: &KeyRequest) -> Result<[u8; 32]> {
+        let td_report = match self.measurement()? {
+            Report::Tdx(report) => report,
+            Report::Tpm(_) => unreachable!("TdxProvider::measurement always returns Report::Tdx"),
+        };
+        let measurement = td_report.measurement_digest();
+
+        if self.simulate {
+            return crate::keyrequest::simulate_sealing_key(request, &measurement);
+        }
+
+        #[cfg(feature = "tdx-guest")]
+        {
+            return crate::keyrequest::get_sealing_key(request);
+        }
+
+        #[cfg(not(feature = "tdx-guest"))]
+        anyhow::bail!("TDG.KEY.REQUEST requires the 'tdx-guest' feature; pass --simulate to use a stub key instead")
+    }
+}
+
+/// Confirm this TD actually supports sealing before trusting a real
+/// TDG.MR.REPORT/TDG.KEY.REQUEST result: `TDX_FEATURES0.SEALING` must be set,
+/// and `ATTRIBUTES.MIGRATABLE` must be clear (a migratable TD's sealing key
+/// isn't guaranteed reproducible on whatever host it migrates to).
+#[cfg(feature = "tdx-guest")]
+fn check_tdx_sealing_available() -> Result<()> {
+    let td_info = get_tdinfo().map_err(|e| anyhow::anyhow!("failed to get TDX info: {:?}", e))?;
+
+    if td_info.tdx_features0 & TDX_FEATURES0_SEALING_BIT == 0 {
+        anyhow::bail!("TDX sealing is not available (TDX_FEATURES0.SEALING = 0)");
+    }
+    if td_info.tdx_attributes & TDX_ATTRIBUTES_MIGRATABLE_BIT != 0 {
+        anyhow::bail!("TDX sealing is not available (ATTRIBUTES.MIGRATABLE = 1)");
+    }
+
+    Ok(())
+}
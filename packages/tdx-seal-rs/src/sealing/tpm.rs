@@ -0,0 +1,317 @@
+//! `SealingProvider` implementation backed by a TPM 2.0, for deployments
+//! without TDX. The PCR selection plays the role the TD measurement plays
+//! for the TDX backend: a primary key created under the owner hierarchy is
+//! deterministic for a given template, and binding a sealed data object to
+//! a PCR policy means it can only be unsealed when those PCRs match.
+
+use anyhow::Result;
+#[cfg(feature = "tpm")]
+use anyhow::Context;
+use sha2::{Digest, Sha256};
+
+use super::{Report, SealingProvider, TpmPcrReport};
+use crate::keyrequest::KeyRequest;
+
+#[cfg(feature = "tpm")]
+use tss_esapi::{
+    abstraction::pcr::read_all,
+    attributes::ObjectAttributesBuilder,
+    interface_types::{algorithm::HashingAlgorithm, resource_handles::Hierarchy},
+    structures::{pcr_slot::PcrSlot, Digest as TpmDigest, PcrSelectionListBuilder, SensitiveData},
+    tcti_ldr::TctiNameConf,
+    Context,
+};
+
+/// TPM 2.0 sealing backend, bound to a caller-supplied PCR selection.
+pub struct TpmProvider {
+    pub pcrs: Vec<u8>,
+    pub simulate: bool,
+}
+
+impl SealingProvider for TpmProvider {
+    fn name(&self) -> &'static str {
+        "tpm"
+    }
+
+    fn available(&self) -> bool {
+        if self.simulate {
+            return true;
+        }
+
+        #[cfg(feature = "tpm")]
+        {
+            return open_context().is_ok();
+        }
+
+        #[cfg(not(feature = "tpm"))]
+        false
+    }
+
+    fn measurement(&self) -> Result<Report> {
+        if self.simulate {
+            return Ok(Report::Tpm(TpmPcrReport {
+                pcrs: self
+                    .pcrs
+                    .iter()
+                    .map(|&index| {
+                        let mut digest = [0u8; 32];
+                        digest[0] = index;
+                        (index, digest)
+                    })
+                    .collect(),
+            }));
+        }
+
+        #[cfg(feature = "tpm")]
+        {
+            let mut ctx = open_context()?;
+            let selection = pcr_selection(&self.pcrs)?;
+            let (_update_counter, _selection, digests) =
+                read_all(&mut ctx, selection).context("failed to read TPM PCRs")?;
+
+            let pcrs = self
+                .pcrs
+                .iter()
+                .zip(digests.value())
+                .map(|(&index, digest)| {
+                    let mut bytes = [0u8; 32];
+                    let value = digest.value();
+                    bytes[..value.len().min(32)].copy_from_slice(&value[..value.len().min(32)]);
+                    (index, bytes)
+                })
+                .collect();
+
+            return Ok(Report::Tpm(TpmPcrReport { pcrs }));
+        }
+
+        #[cfg(not(feature = "tpm"))]
+        anyhow::bail!("TPM PCR read requires the 'tpm' feature; pass --simulate to use stub PCR values instead")
+    }
+
+    fn seal_key(&self, request: &KeyRequest) -> Result<[u8; 32]> {
+        let report = self.measurement()?;
+        let pcrs = match report {
+            Report::Tpm(r) => r,
+            Report::Tdx(_) => unreachable!("TpmProvider::measurement always returns Report::Tpm"),
+        };
+
+        if self.simulate {
+            let mut hasher = Sha256::new();
+            hasher.update(b"tpm-simulated-seal");
+            hasher.update(request.keyid);
+            for (index, digest) in &pcrs.pcrs {
+                hasher.update([*index]);
+                hasher.update(digest);
+            }
+            let result = hasher.finalize();
+            let mut key = [0u8; 32];
+            key.copy_from_slice(&result);
+            return Ok(key);
+        }
+
+        #[cfg(feature = "tpm")]
+        {
+            let mut ctx = open_context()?;
+            let selection = pcr_selection(&self.pcrs)?;
+
+            // A primary key under the owner hierarchy created from a fixed
+            // template (here, one parameterized by `keyid`) is deterministic
+            // on a given TPM, so TPM2_CreatePrimary plays the role TDG.KEY.REQUEST
+            // plays for TDX. Its `unique` field is derived by the TPM from its
+            // storage seed and is not reconstructable without hardware access,
+            // so we fold it into the secret below rather than sealing `keyid`
+            // verbatim (which anyone could predict without ever touching a TPM).
+            // The selected PCR digests are folded in too, so the secret
+            // changes when the measured state does.
+            let template = primary_template(&request.keyid)?;
+            let primary = ctx
+                .execute_with_nullauth_session(|ctx| {
+                    ctx.create_primary(Hierarchy::Owner, template, None, None, None, None)
+                })
+                .context("TPM2_CreatePrimary failed")?;
+
+            let mut hasher = Sha256::new();
+            hasher.update(b"tpm-sealed-secret");
+            hasher.update(primary_unique_bytes(&primary.out_public)?);
+            hasher.update(request.keyid);
+            for (index, digest) in &pcrs.pcrs {
+                hasher.update([*index]);
+                hasher.update(digest);
+            }
+            let derived_secret: [u8; 32] = hasher.finalize().into();
+
+            // Seal the derived secret under a PCR policy, then immediately
+            // unseal it in the same call so it never needs to be written to
+            // disk by this step.
+            let policy_digest = compute_pcr_policy_digest(&mut ctx, selection)?;
+            let sealed = create_sealed_object(&mut ctx, primary.key_handle, &policy_digest, &derived_secret)?;
+            let secret = unseal(&mut ctx, primary.key_handle, sealed, selection)?;
+
+            let mut key = [0u8; 32];
+            let len = secret.value().len().min(32);
+            key[..len].copy_from_slice(&secret.value()[..len]);
+            return Ok(key);
+        }
+
+        #[cfg(not(feature = "tpm"))]
+        anyhow::bail!("TPM sealing requires the 'tpm' feature; pass --simulate to use a stub key instead")
+    }
+}
+
+#[cfg(feature = "tpm")]
+fn open_context() -> Result<Context> {
+    let tcti = TctiNameConf::from_environment_variable().context("failed to resolve TPM TCTI from environment")?;
+    Context::new(tcti).context("failed to open TPM ESYS context")
+}
+
+#[cfg(feature = "tpm")]
+fn pcr_selection(pcrs: &[u8]) -> Result<tss_esapi::structures::PcrSelectionList> {
+    let slots: Result<Vec<PcrSlot>> = pcrs
+        .iter()
+        .map(|&index| PcrSlot::try_from(1u32 << index).context("invalid PCR index"))
+        .collect();
+
+    Ok(PcrSelectionListBuilder::new()
+        .with_selection(HashingAlgorithm::Sha256, &slots?)
+        .build()
+        .context("failed to build PCR selection")?)
+}
+
+#[cfg(feature = "tpm")]
+fn primary_template(keyid: &[u8; 32]) -> Result<tss_esapi::structures::Public> {
+    use tss_esapi::{
+        interface_types::algorithm::PublicAlgorithm,
+        structures::{PublicBuilder, PublicKeyedHashParameters, PublicKeyedHashScheme},
+    };
+
+    let object_attributes = ObjectAttributesBuilder::new()
+        .with_fixed_tpm(true)
+        .with_fixed_parent(true)
+        .with_sensitive_data_origin(true)
+        .with_user_with_auth(true)
+        .with_decrypt(true)
+        .with_sign_encrypt(false)
+        .build()
+        .context("failed to build object attributes")?;
+
+    Ok(PublicBuilder::new()
+        .with_public_algorithm(PublicAlgorithm::KeyedHash)
+        .with_name_hashing_algorithm(HashingAlgorithm::Sha256)
+        .with_object_attributes(object_attributes)
+        .with_keyed_hash_parameters(PublicKeyedHashParameters::new(PublicKeyedHashScheme::Null))
+        .with_keyed_hash_unique_identifier(TpmDigest::try_from(keyid.to_vec())?)
+        .build()
+        .context("failed to build primary template")?)
+}
+
+/// Extract the `unique` field from a keyed-hash primary's public area: the
+/// TPM derives this from its storage primary seed and the template at
+/// TPM2_CreatePrimary time, so it's deterministic for a given TPM but
+/// unknown to anyone without hardware access.
+#[cfg(feature = "tpm")]
+fn primary_unique_bytes(public: &tss_esapi::structures::Public) -> Result<Vec<u8>> {
+    match public {
+        tss_esapi::structures::Public::KeyedHash { unique, .. } => Ok(unique.value().to_vec()),
+        _ => anyhow::bail!("expected a keyed-hash primary public area"),
+    }
+}
+
+#[cfg(feature = "tpm")]
+fn compute_pcr_policy_digest(
+    ctx: &mut Context,
+    selection: tss_esapi::structures::PcrSelectionList,
+) -> Result<TpmDigest> {
+    use tss_esapi::{
+        constants::SessionType, interface_types::session_handles::PolicySession, structures::SymmetricDefinition,
+    };
+
+    let session = ctx
+        .start_auth_session(
+            None,
+            None,
+            None,
+            SessionType::Trial,
+            SymmetricDefinition::AES_128_CFB,
+            HashingAlgorithm::Sha256,
+        )
+        .context("failed to start TPM trial policy session")?
+        .context("TPM returned no session handle")?;
+    let policy_session = PolicySession::try_from(session)?;
+
+    ctx.policy_pcr(policy_session, TpmDigest::default(), selection)
+        .context("TPM2_PolicyPCR failed")?;
+
+    ctx.policy_get_digest(policy_session).context("TPM2_PolicyGetDigest failed")
+}
+
+#[cfg(feature = "tpm")]
+fn create_sealed_object(
+    ctx: &mut Context,
+    parent: tss_esapi::handles::KeyHandle,
+    policy_digest: &TpmDigest,
+    secret: &[u8; 32],
+) -> Result<tss_esapi::structures::CreateKeyResult> {
+    use tss_esapi::structures::{PublicBuilder, PublicKeyedHashParameters, PublicKeyedHashScheme};
+
+    let object_attributes = ObjectAttributesBuilder::new()
+        .with_fixed_tpm(true)
+        .with_fixed_parent(true)
+        .with_user_with_auth(false)
+        .build()
+        .context("failed to build sealed object attributes")?;
+
+    let public = PublicBuilder::new()
+        .with_public_algorithm(tss_esapi::interface_types::algorithm::PublicAlgorithm::KeyedHash)
+        .with_name_hashing_algorithm(HashingAlgorithm::Sha256)
+        .with_object_attributes(object_attributes)
+        .with_keyed_hash_parameters(PublicKeyedHashParameters::new(PublicKeyedHashScheme::Null))
+        .with_auth_policy(policy_digest.clone())
+        .build()
+        .context("failed to build sealed object public area")?;
+
+    ctx.execute_with_nullauth_session(|ctx| {
+        ctx.create(
+            parent,
+            public,
+            None,
+            Some(SensitiveData::try_from(secret.to_vec())?),
+            None,
+            None,
+        )
+    })
+    .context("TPM2_Create (seal) failed")
+}
+
+#[cfg(feature = "tpm")]
+fn unseal(
+    ctx: &mut Context,
+    parent: tss_esapi::handles::KeyHandle,
+    sealed: tss_esapi::structures::CreateKeyResult,
+    selection: tss_esapi::structures::PcrSelectionList,
+) -> Result<SensitiveData> {
+    use tss_esapi::{
+        constants::SessionType, interface_types::session_handles::PolicySession, structures::SymmetricDefinition,
+    };
+
+    let object_handle = ctx
+        .execute_with_nullauth_session(|ctx| ctx.load(parent, sealed.out_private, sealed.out_public))
+        .context("TPM2_Load failed")?;
+
+    let session = ctx
+        .start_auth_session(
+            None,
+            None,
+            None,
+            SessionType::Policy,
+            SymmetricDefinition::AES_128_CFB,
+            HashingAlgorithm::Sha256,
+        )
+        .context("failed to start TPM policy session")?
+        .context("TPM returned no session handle")?;
+    let policy_session = PolicySession::try_from(session)?;
+    ctx.policy_pcr(policy_session, TpmDigest::default(), selection)
+        .context("TPM2_PolicyPCR failed")?;
+
+    ctx.execute_with_session(Some(session), |ctx| ctx.unseal(object_handle.into()))
+        .context("TPM2_Unseal failed")
+}